@@ -1,15 +1,21 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     fmt,
     fs::{self, File},
-    io::{self, prelude::*, BufRead, BufReader, Read, SeekFrom},
+    io::{self, prelude::*, BufRead, BufReader, ErrorKind, Read, SeekFrom},
     ops::Deref,
     path::PathBuf,
     str::{self, FromStr},
+    time::Duration,
 };
 
+use chrono::{DateTime as ChronoDateTime, NaiveDate, NaiveDateTime};
+use argon2::{
+    password_hash::SaltString,
+    Argon2, PasswordHasher,
+};
 use clap::Parser;
-use color_eyre::eyre::{bail, ContextCompat, Result};
+use color_eyre::eyre::{bail, eyre, ContextCompat, Result};
 use fake::{
     faker::{
         address::en::{CityName, CountryName, SecondaryAddress, StateName, StreetName, ZipCode},
@@ -19,11 +25,14 @@ use fake::{
         phone_number::en::PhoneNumber,
         time::en::{Date, DateTime},
     },
-    Fake, Faker,
+    Fake,
 };
-use rand::Rng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde_json::{json, Value};
 use sqlparser::{dialect::MySqlDialect, parser::ParserError};
+use futures::StreamExt;
+use sqlx::{any::AnyConnection, Column, Connection, Row};
 use thiserror::Error;
 use tree_sitter::{Language, Node, Query, QueryCursor};
 
@@ -36,12 +45,57 @@ extern "C" {
 #[command(author, version, about, long_about = None)]
 struct Cli {
     /// SQL dump
-    input: PathBuf,
+    input: Option<PathBuf>,
+
+    /// Live database URL to stream from instead of a dump file
+    ///
+    /// Connects with `sqlx`, dumps every base table and anonymizes the result
+    /// through the same directive pipeline. Mutually exclusive with the dump
+    /// argument.
+    #[arg(short, long, conflicts_with = "input")]
+    database: Option<String>,
 
     /// Tree query
     #[arg(short, long)]
     query: Option<PathBuf>,
 
+    /// Mapping from capture name to directive expression
+    ///
+    /// Tree-sitter capture names may only contain identifier characters, so an
+    /// expression like `@password("test1234", bcrypt, cost=10)` cannot be
+    /// written directly inside the query. Instead, give the capture a plain
+    /// name such as `@customer_password` and put one `name = expression` pair
+    /// per line here, e.g. `customer_password = password("test1234", bcrypt,
+    /// cost=10)`. Captures with no matching entry fall back to parsing their
+    /// own name as the expression, so bare directives like `@email` still
+    /// work without a mapping file.
+    #[arg(long)]
+    directives: Option<PathBuf>,
+
+    /// Secret key enabling deterministic, referentially-consistent output
+    ///
+    /// When set, every fake value is derived from a BLAKE3 hash of the key, the
+    /// capture name and the original bytes, so identical inputs under the same
+    /// capture name map to identical outputs across the whole file and across
+    /// runs — preserving foreign keys and joins.
+    #[arg(short, long)]
+    key: Option<String>,
+
+    /// Preserve the ordering of numeric and date columns
+    ///
+    /// Maps each distinct original value of an `u32`, `i64` or `date` capture
+    /// onto a strictly increasing fake value, so range queries, `ORDER BY` and
+    /// `BETWEEN` stay meaningful on the anonymized dump.
+    #[arg(short, long)]
+    order_preserving: bool,
+
+    /// Plaintext hashed by `password` captures
+    ///
+    /// Used directly by bare `password` captures and as the default plaintext
+    /// for `@password(...)` invocations that omit one.
+    #[arg(short, long, default_value = "password")]
+    password: String,
+
     /// BufReader buffer size
     #[arg(short, long, default_value = "8192")]
     buffer_size: usize,
@@ -54,7 +108,7 @@ fn main() -> Result<()> {
     let mut parser = tree_sitter::Parser::new();
     let language = unsafe { tree_sitter_sql() };
     parser.set_language(language).unwrap();
-    let file = File::open(cli.input)?;
+    let file = Source::from_cli(&cli)?.open()?;
     let mut reader = BufReader::with_capacity(cli.buffer_size, file);
 
     let tree = parser
@@ -107,11 +161,94 @@ fn main() -> Result<()> {
     let query = Query::new(language, &query_source)?;
     let capture_names = query.capture_names();
 
-    let directives = capture_names
+    let directives_source = if let Some(directives) = cli.directives {
+        fs::read_to_string(directives)?
+    } else {
+        String::new()
+    };
+
+    let directive_exprs = parse_directives(&directives_source)?;
+
+    // A bare capture name that is neither a known directive nor a
+    // `--directives` entry parses as a lone `Sym`, which is only meaningful as
+    // a nested argument (e.g. the algorithm name in `password(...)`). Treat it
+    // as unrecognized at the top level, same as the baseline: leave the node
+    // untouched instead of stringifying the capture name over it.
+    let exprs = capture_names
         .iter()
-        .map(|n| n.parse::<Directive>().ok())
+        .map(|n| {
+            directive_exprs.get(*n).cloned().or_else(|| {
+                n.parse::<Expr>()
+                    .ok()
+                    .filter(|expr| !matches!(expr, Expr::Sym(_)))
+            })
+        })
         .collect::<Vec<_>>();
 
+    // Under `--key` the order-preserving mapping is seeded from the key, like the
+    // keyed cache, so that its random base and increments are reproducible
+    // across runs; without a key it is seeded from entropy.
+    let mut op_rng = match &cli.key {
+        Some(key) => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(key.as_bytes());
+            hasher.update(b"order-preserving");
+            ChaCha20Rng::from_seed(*hasher.finalize().as_bytes())
+        }
+        None => ChaCha20Rng::from_entropy(),
+    };
+
+    // Pass one for order preservation: collect the distinct original values
+    // per order-preserving directive, then build a monotonic original -> fake
+    // mapping over their sorted distinct values.
+    let op_maps = if cli.order_preserving {
+        let mut values: HashMap<Directive, HashSet<i64>> = HashMap::new();
+        let mut op_cursor = QueryCursor::new();
+        let op_matches = op_cursor.matches(&query, root_node, |node: Node| {
+            texts.get(&node.id()).map(|v| &v[..]).into_iter()
+        });
+
+        for query_match in op_matches {
+            for query_capture in query_match.captures {
+                let Some(directive) = exprs[query_capture.index as usize]
+                    .as_ref()
+                    .and_then(Expr::as_directive)
+                    .filter(|d| d.order_preserving())
+                else {
+                    continue;
+                };
+
+                let node = query_capture.node;
+                let start_byte = node.start_byte();
+                let limit = node.end_byte() - start_byte;
+                reader.seek(SeekFrom::Start(start_byte as u64))?;
+                let mut handle = reader.take(limit as u64);
+                let mut buf = vec![0; limit];
+                handle.read_exact(&mut buf)?;
+                reader = handle.into_inner();
+
+                if let Some(key) = order_preserving_key(directive, &buf) {
+                    values.entry(directive).or_default().insert(key);
+                }
+            }
+        }
+
+        let mut directives = values.keys().copied().collect::<Vec<_>>();
+        directives.sort_unstable();
+
+        let maps = directives
+            .into_iter()
+            .map(|directive| {
+                let set = &values[&directive];
+                (directive, build_order_preserving_map(directive, set, &mut op_rng))
+            })
+            .collect::<HashMap<_, _>>();
+
+        Some(maps)
+    } else {
+        None
+    };
+
     let mut query_cursor = QueryCursor::new();
 
     let query_matches = query_cursor.matches(&query, root_node, |node: Node| {
@@ -120,11 +257,13 @@ fn main() -> Result<()> {
 
     let mut stdout = io::stdout().lock();
     let mut rng = rand::thread_rng();
+    let mut cache: HashMap<(String, Vec<u8>), String> = HashMap::new();
     reader.rewind()?;
 
     for query_match in query_matches {
         for query_capture in query_match.captures {
-            let Some(directive) = directives[query_capture.index as usize] else {
+            let index = query_capture.index as usize;
+            let Some(expr) = &exprs[index] else {
                 continue;
             };
 
@@ -146,7 +285,40 @@ fn main() -> Result<()> {
                 );
             }
 
-            reader = anonymize(directive, node, reader, &mut stdout, &mut rng)?;
+            let limit = node.end_byte() - node.start_byte();
+            let mut handle = reader.take(limit as u64);
+            let mut original = vec![0; limit];
+            handle.read_exact(&mut original)?;
+            reader = handle.into_inner();
+
+            let order_preserved = match (op_maps.as_ref(), expr.as_directive()) {
+                (Some(maps), Some(directive)) => {
+                    apply_order_preserving(directive, &original, maps)?
+                }
+                _ => None,
+            };
+
+            let rendered = match order_preserved {
+                Some(rendered) => rendered,
+                None => match &cli.key {
+                    Some(key) => match cache.entry((capture_names[index].to_string(), original.clone())) {
+                        Entry::Occupied(entry) => entry.get().clone(),
+                        Entry::Vacant(entry) => {
+                            let mut hasher = blake3::Hasher::new();
+                            hasher.update(key.as_bytes());
+                            hasher.update(capture_names[index].as_bytes());
+                            hasher.update(&original);
+                            let seed = *hasher.finalize().as_bytes();
+                            let mut rng = ChaCha20Rng::from_seed(seed);
+                            let value = eval(expr, &original, &mut rng, &cli.password)?;
+                            entry.insert(render_sql(&value)).clone()
+                        }
+                    },
+                    None => render_sql(&eval(expr, &original, &mut rng, &cli.password)?),
+                },
+            };
+
+            write!(stdout, "{}", rendered)?;
         }
     }
 
@@ -155,18 +327,237 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Where the SQL to anonymize comes from.
+enum Source {
+    /// A SQL dump file on disk.
+    File(PathBuf),
+    /// A live database, connected to and dumped on demand.
+    Database(String),
+}
+
+impl Source {
+    fn from_cli(cli: &Cli) -> Result<Self> {
+        match (&cli.input, &cli.database) {
+            (Some(path), None) => Ok(Self::File(path.clone())),
+            (None, Some(url)) => Ok(Self::Database(url.clone())),
+            (None, None) => bail!("provide a dump file or --database <URL>"),
+            (Some(_), Some(_)) => bail!("a dump file and --database are mutually exclusive"),
+        }
+    }
+
+    /// Materialize the source as a seekable SQL dump for the tree-sitter pass.
+    fn open(&self) -> Result<File> {
+        match self {
+            Self::File(path) => Ok(File::open(path)?),
+            Self::Database(url) => dump_database(url),
+        }
+    }
+}
+
+/// Connect to a live database, dump every base table as `INSERT` statements
+/// into a temporary file and hand it back rewound for the anonymizer.
+fn dump_database(url: &str) -> Result<File> {
+    let dialect = SqlDialect::from_url(url)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let mut file = tempfile::tempfile()?;
+
+    runtime.block_on(async {
+        sqlx::any::install_default_drivers();
+        let mut conn = connect_with_retry(url).await?;
+
+        for table in list_tables(&mut conn, dialect).await? {
+            dump_table(&mut conn, dialect, &table, &mut file).await?;
+        }
+
+        conn.close().await?;
+        Ok::<_, color_eyre::Report>(())
+    })?;
+
+    file.rewind()?;
+    Ok(file)
+}
+
+/// Which of the two supported SQL flavors a live connection speaks, so table
+/// listing and identifier quoting can be done correctly for either.
+#[derive(Clone, Copy)]
+enum SqlDialect {
+    MySql,
+    Postgres,
+}
+
+impl SqlDialect {
+    /// Infer the dialect from the connection URL's scheme.
+    fn from_url(url: &str) -> Result<Self> {
+        match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("mysql") => Ok(Self::MySql),
+            Some("postgres" | "postgresql") => Ok(Self::Postgres),
+            _ => bail!("unsupported database URL `{url}`, expected a mysql:// or postgres:// scheme"),
+        }
+    }
+
+    /// Quote a table identifier per the dialect's convention.
+    fn quote_ident(self, ident: &str) -> String {
+        match self {
+            Self::MySql => format!("`{ident}`"),
+            Self::Postgres => format!("\"{ident}\""),
+        }
+    }
+
+    /// A query listing base tables in the current database only, excluding
+    /// system catalogs such as `information_schema`, `mysql`, or `pg_catalog`.
+    /// On Postgres only tables in the `public` schema are listed.
+    fn list_tables_query(self) -> &'static str {
+        match self {
+            Self::MySql => {
+                "SELECT table_name FROM information_schema.tables \
+                 WHERE table_type = 'BASE TABLE' AND table_schema = DATABASE()"
+            }
+            Self::Postgres => {
+                "SELECT table_name FROM information_schema.tables \
+                 WHERE table_type = 'BASE TABLE' AND table_schema = 'public'"
+            }
+        }
+    }
+}
+
+/// Open a connection, retrying transient connection errors with exponential
+/// backoff. Non-transient failures abort immediately.
+async fn connect_with_retry(url: &str) -> Result<AnyConnection> {
+    const MAX_RETRIES: u32 = 8;
+    const BASE_DELAY: Duration = Duration::from_millis(100);
+
+    let mut attempt = 0;
+
+    loop {
+        match AnyConnection::connect(url).await {
+            Ok(conn) => return Ok(conn),
+            Err(err) if attempt < MAX_RETRIES && is_transient(&err) => {
+                let delay = BASE_DELAY * 2u32.pow(attempt);
+                eprintln!(
+                    "connection attempt {} failed ({err}); retrying in {delay:?}",
+                    attempt + 1
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Whether a connection error is a transient network hiccup worth retrying.
+fn is_transient(err: &sqlx::Error) -> bool {
+    matches!(
+        err,
+        sqlx::Error::Io(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+            )
+    )
+}
+
+/// List the names of every base table in the connected database's own schema,
+/// skipping system catalogs.
+async fn list_tables(conn: &mut AnyConnection, dialect: SqlDialect) -> Result<Vec<String>> {
+    let rows = sqlx::query(dialect.list_tables_query())
+        .fetch_all(&mut *conn)
+        .await?;
+
+    rows.iter()
+        .map(|row| Ok(row.try_get::<String, _>(0)?))
+        .collect()
+}
+
+/// Stream a single table and append one `INSERT` statement per row.
+async fn dump_table(
+    conn: &mut AnyConnection,
+    dialect: SqlDialect,
+    table: &str,
+    file: &mut File,
+) -> Result<()> {
+    let ident = dialect.quote_ident(table);
+    let sql = format!("SELECT * FROM {ident}");
+    let mut rows = sqlx::query(&sql).fetch(&mut *conn);
+
+    while let Some(row) = rows.next().await {
+        let row = row?;
+        let values = (0..row.columns().len())
+            .map(|i| encode_cell(&row, i))
+            .collect::<Result<Vec<_>>>()?
+            .join(", ");
+
+        writeln!(file, "INSERT INTO {ident} VALUES ({values});")?;
+    }
+
+    Ok(())
+}
+
+/// Encode a single cell as a SQL literal, probing column types from most to
+/// least specific. Fails loudly (rather than silently emitting `NULL`) when a
+/// cell's type matches none of the probes, so undecodable data is reported
+/// instead of dropped.
+fn encode_cell(row: &sqlx::any::AnyRow, i: usize) -> Result<String> {
+    if let Ok(value) = row.try_get::<Option<i64>, _>(i) {
+        return Ok(value.map_or_else(|| "NULL".to_string(), |n| n.to_string()));
+    }
+
+    if let Ok(value) = row.try_get::<Option<f64>, _>(i) {
+        return Ok(value.map_or_else(|| "NULL".to_string(), |n| n.to_string()));
+    }
+
+    if let Ok(value) = row.try_get::<Option<bool>, _>(i) {
+        return Ok(value.map_or_else(|| "NULL".to_string(), |b| u8::from(b).to_string()));
+    }
+
+    if let Ok(value) = row.try_get::<Option<NaiveDateTime>, _>(i) {
+        return Ok(value.map_or_else(
+            || "NULL".to_string(),
+            |dt| MySqlString(dt.format("%Y-%m-%d %H:%M:%S").to_string()).to_string(),
+        ));
+    }
+
+    if let Ok(value) = row.try_get::<Option<NaiveDate>, _>(i) {
+        return Ok(value.map_or_else(
+            || "NULL".to_string(),
+            |date| MySqlString(date.format("%Y-%m-%d").to_string()).to_string(),
+        ));
+    }
+
+    if let Ok(value) = row.try_get::<Option<String>, _>(i) {
+        return Ok(value.map_or_else(|| "NULL".to_string(), |s| MySqlString(s).to_string()));
+    }
+
+    if let Ok(value) = row.try_get::<Option<Vec<u8>>, _>(i) {
+        return Ok(value.map_or_else(
+            || "NULL".to_string(),
+            |bytes| format!("X'{}'", hex_encode(&bytes)),
+        ));
+    }
+
+    let column = row.columns().get(i).map(|c| c.name().to_string());
+    bail!("could not decode column {column:?} (index {i}) as any known type")
+}
+
+/// Render bytes as an uppercase hex string for a MySQL `X'...'` blob literal.
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02X}")).collect()
+}
+
 #[derive(Clone, Copy, Error, Debug)]
 #[error("invalid directive")]
 struct DirectiveError;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum Directive {
     Address,
-    BiologicalSex,
     Bic,
     Date,
     Email,
     FirstName,
+    I64,
     Iban,
     LastName,
     Name,
@@ -177,17 +568,36 @@ enum Directive {
     VatNo,
 }
 
+impl Directive {
+    /// Whether the directive participates in the order-preserving mapping.
+    fn order_preserving(self) -> bool {
+        matches!(self, Self::U32 | Self::I64 | Self::Date)
+    }
+
+    /// Upper bound for fake values the order-preserving mapping may assign to
+    /// this directive, so e.g. a `u32` column never maps past `u32::MAX` and
+    /// overflows on reload.
+    fn order_preserving_max(self) -> i64 {
+        match self {
+            Self::U32 => i64::from(u32::MAX),
+            // 9999-12-31 23:59:59 UTC, so mapped timestamps stay renderable dates.
+            Self::Date => 253_402_300_799,
+            _ => i64::MAX / 2,
+        }
+    }
+}
+
 impl FromStr for Directive {
     type Err = DirectiveError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "address" => Ok(Self::Address),
-            "biological_sex" => Ok(Self::BiologicalSex),
             "bic" => Ok(Self::Bic),
             "date" => Ok(Self::Date),
             "email" => Ok(Self::Email),
             "first_name" => Ok(Self::FirstName),
+            "i64" => Ok(Self::I64),
             "iban" => Ok(Self::Iban),
             "last_name" => Ok(Self::LastName),
             "name" => Ok(Self::Name),
@@ -201,17 +611,30 @@ impl FromStr for Directive {
     }
 }
 
-fn anonymize(
+/// A bare `@biological_sex` capture, kept working after the directive was
+/// subsumed by the general-purpose `enum` built-in, expanding to the same
+/// 50/50 `Male`/`Female` split the old directive produced.
+fn biological_sex_alias() -> Expr {
+    Expr::Call(
+        "enum".to_string(),
+        vec![
+            Expr::Lit(Value::Array(vec![Value::String("Male".to_string()), json!(0.5)])),
+            Expr::Lit(Value::Array(vec![Value::String("Female".to_string()), json!(0.5)])),
+        ],
+    )
+}
+
+/// The logical value produced for a directive, before it is rendered into a SQL
+/// token. Keeping it as a `Value` lets composition functions like `concat` work
+/// on the raw contents instead of the quoted literal.
+fn fake_value(
     directive: Directive,
-    node: Node,
-    mut reader: BufReader<File>,
-    writer: &mut impl Write,
+    original: &[u8],
     rng: &mut impl Rng,
-) -> Result<BufReader<File>> {
-    match directive {
+    password: &str,
+) -> Result<Value> {
+    let value = match directive {
         Directive::Address => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-
             let street_name: String = StreetName().fake_with_rng(rng);
             let street_details: String = SecondaryAddress().fake_with_rng(rng);
             let zip_code: String = ZipCode().fake_with_rng(rng);
@@ -228,54 +651,18 @@ fn anonymize(
                 "state": state,
             });
 
-            write!(writer, "{}", MySqlString(address.to_string()))?;
-        }
-        Directive::BiologicalSex => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let biological_sex: &str = if Faker.fake() { "'Male'" } else { "'Female'" };
-            write!(writer, "{}", biological_sex)?;
-        }
-        Directive::Bic => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let bic: String = Bic().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(bic))?;
-        }
-        Directive::Date => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let date: String = Date().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(date))?;
-        }
-        Directive::Email => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let email: String = SafeEmail().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(email))?;
-        }
-        Directive::FirstName => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let first_name: String = FirstName().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(first_name))?;
-        }
-        Directive::Iban => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            write!(writer, "'AT01234567890123456789'")?;
-        }
-        Directive::LastName => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let last_name: String = LastName().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(last_name))?;
-        }
-        Directive::Name => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let name: String = Name().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(name))?;
+            Value::String(address.to_string())
         }
+        Directive::Bic => Value::String(Bic().fake_with_rng(rng)),
+        Directive::Date => Value::String(Date().fake_with_rng(rng)),
+        Directive::Email => Value::String(SafeEmail().fake_with_rng(rng)),
+        Directive::FirstName => Value::String(FirstName().fake_with_rng(rng)),
+        Directive::I64 => json!(rng.gen::<i64>()),
+        Directive::Iban => Value::String("AT01234567890123456789".to_string()),
+        Directive::LastName => Value::String(LastName().fake_with_rng(rng)),
+        Directive::Name => Value::String(Name().fake_with_rng(rng)),
         Directive::Order => {
-            let limit = node.end_byte() - node.start_byte();
-            let mut handle = reader.take(limit as u64);
-            let mut buf = vec![0; limit];
-            handle.read_exact(&mut buf)?;
-            reader = handle.into_inner();
-            let string: MySqlString = str::from_utf8(&buf)?.parse()?;
+            let string: MySqlString = str::from_utf8(original)?.parse()?;
 
             let mut value: Value = serde_json::from_str(&string)?;
 
@@ -288,7 +675,7 @@ fn anonymize(
             let first_name: String = FirstName().fake_with_rng(rng);
             let last_name: String = LastName().fake_with_rng(rng);
             let email: String = SafeEmail().fake_with_rng(rng);
-            let gender: &str = if Faker.fake() { "male" } else { "female" };
+            let gender: &str = if rng.gen() { "male" } else { "female" };
             let height: u32 = rng.next_u32();
             let weight: u32 = rng.next_u32();
             let birth_date: String = DateTime().fake_with_rng(rng);
@@ -303,32 +690,570 @@ fn anonymize(
                 "birthDate": birth_date,
             });
 
-            write!(writer, "{}", MySqlString(value.to_string()))?;
+            Value::String(value.to_string())
         }
         Directive::Password => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-
-            write!(
-                writer,
-                "'$2y$10$xOGO.s9/T06bIuCydNED7up5JWlXWp/kK7C8DC76kWyYrB5s9rnAu'"
+            let hash = hash_password(
+                password,
+                PasswordAlgorithm::Bcrypt {
+                    cost: bcrypt::DEFAULT_COST,
+                },
+                rng,
             )?;
+            Value::String(hash)
+        }
+        Directive::PhoneNumber => Value::String(PhoneNumber().fake_with_rng(rng)),
+        Directive::U32 => json!(rng.next_u32()),
+        Directive::VatNo => Value::String("AT01234567".to_string()),
+    };
+
+    Ok(value)
+}
+
+/// A directive expression, parsed either straight from a capture name (for
+/// bare directives such as `email`) or from the right-hand side of a
+/// `--directives` mapping line (for calls, which capture names cannot encode).
+/// `Field` is a bare directive, `Call` a built-in function application and
+/// `Lit` a literal argument.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Call(String, Vec<Expr>),
+    Kwarg(String, Box<Expr>),
+    Lit(Value),
+    Field(Directive),
+    Sym(String),
+}
+
+impl Expr {
+    /// The directive this expression resolves to when it is a bare field, used
+    /// by the order-preserving pass which only maps single-column directives.
+    fn as_directive(&self) -> Option<Directive> {
+        match self {
+            Self::Field(directive) => Some(*directive),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser::new(s);
+        let expr = parser.parse_expr()?;
+        parser.finish()?;
+        Ok(expr)
+    }
+}
+
+/// Parse a `--directives` file into a capture name -> expression table.
+///
+/// Each non-blank, non-`#`-comment line is a `name = expression` pair, kept
+/// out of the query itself because tree-sitter capture names only allow
+/// identifier characters and can't hold the parens, commas and quotes a call
+/// needs.
+fn parse_directives(source: &str) -> Result<HashMap<String, Expr>> {
+    let mut map = HashMap::new();
+
+    for line in source.lines() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, expr) = line
+            .split_once('=')
+            .wrap_err_with(|| format!("invalid directive mapping line: {line:?}"))?;
+
+        map.insert(name.trim().to_string(), expr.trim().parse()?);
+    }
+
+    Ok(map)
+}
+
+#[derive(Clone, Error, Debug)]
+#[error("invalid directive expression: {0}")]
+struct ExprError(String);
+
+/// A tiny recursive-descent parser for the capture-name expression language.
+struct ExprParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.input.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ExprError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(b'"') => self.parse_string().map(|s| Expr::Lit(Value::String(s))),
+            Some(b'[') => self.parse_array(),
+            Some(c) if c == b'-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) if c == b'_' || c.is_ascii_alphabetic() => self.parse_ident_or_call(),
+            other => Err(ExprError(format!("unexpected {other:?}"))),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ExprError> {
+        self.pos += 1; // opening quote
+        let mut out = String::new();
+
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b'"') => out.push('"'),
+                        Some(b'\\') => out.push('\\'),
+                        Some(b'n') => out.push('\n'),
+                        Some(b't') => out.push('\t'),
+                        other => return Err(ExprError(format!("invalid escape {other:?}"))),
+                    }
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    out.push(c as char);
+                    self.pos += 1;
+                }
+                None => return Err(ExprError("unterminated string".into())),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Expr, ExprError> {
+        self.pos += 1; // opening bracket
+        let mut items = Vec::new();
+        self.skip_whitespace();
+
+        if self.peek() != Some(b']') {
+            loop {
+                match self.parse_expr()? {
+                    Expr::Lit(value) => items.push(value),
+                    _ => return Err(ExprError("array elements must be literals".into())),
+                }
+
+                self.skip_whitespace();
+
+                match self.peek() {
+                    Some(b',') => {
+                        self.pos += 1;
+                    }
+                    Some(b']') => break,
+                    other => return Err(ExprError(format!("expected `,` or `]`, got {other:?}"))),
+                }
+            }
+        }
+
+        self.pos += 1; // closing bracket
+        Ok(Expr::Lit(Value::Array(items)))
+    }
+
+    fn parse_number(&mut self) -> Result<Expr, ExprError> {
+        let start = self.pos;
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || c == b'.')
+        {
+            self.pos += 1;
+        }
+
+        let text = str::from_utf8(&self.input[start..self.pos]).unwrap();
+        let value: Value = text
+            .parse::<serde_json::Number>()
+            .map(Value::Number)
+            .map_err(|_| ExprError(format!("invalid number `{text}`")))?;
+
+        Ok(Expr::Lit(value))
+    }
+
+    fn parse_ident(&mut self) -> String {
+        let start = self.pos;
+
+        while self
+            .peek()
+            .is_some_and(|c| c == b'_' || c.is_ascii_alphanumeric())
+        {
+            self.pos += 1;
+        }
+
+        str::from_utf8(&self.input[start..self.pos])
+            .unwrap()
+            .to_string()
+    }
+
+    fn parse_ident_or_call(&mut self) -> Result<Expr, ExprError> {
+        let name = self.parse_ident();
+        self.skip_whitespace();
+
+        // A keyword argument such as `cost=10`.
+        if self.peek() == Some(b'=') {
+            self.pos += 1;
+            let value = self.parse_expr()?;
+            return Ok(Expr::Kwarg(name, Box::new(value)));
+        }
+
+        // A bare word: a known directive, a deprecated alias expanding to a
+        // built-in call, otherwise a symbol (e.g. `bcrypt`).
+        if self.peek() != Some(b'(') {
+            return Ok(match name.parse::<Directive>() {
+                Ok(directive) => Expr::Field(directive),
+                Err(_) => match name.as_str() {
+                    "biological_sex" => biological_sex_alias(),
+                    _ => Expr::Sym(name),
+                },
+            });
+        }
+
+        self.pos += 1; // opening paren
+        let mut args = Vec::new();
+        self.skip_whitespace();
+
+        if self.peek() != Some(b')') {
+            loop {
+                args.push(self.parse_expr()?);
+                self.skip_whitespace();
+
+                match self.peek() {
+                    Some(b',') => {
+                        self.pos += 1;
+                    }
+                    Some(b')') => break,
+                    other => return Err(ExprError(format!("expected `,` or `)`, got {other:?}"))),
+                }
+            }
+        }
+
+        self.pos += 1; // closing paren
+        Ok(Expr::Call(name, args))
+    }
+
+    fn finish(&mut self) -> Result<(), ExprError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            None => Ok(()),
+            Some(c) => Err(ExprError(format!("trailing input starting at {:?}", c as char))),
+        }
+    }
+}
+
+/// Evaluate an expression against a captured node, yielding its logical value.
+fn eval(expr: &Expr, original: &[u8], rng: &mut impl Rng, password: &str) -> Result<Value> {
+    match expr {
+        Expr::Lit(value) => Ok(value.clone()),
+        Expr::Sym(name) => Ok(Value::String(name.clone())),
+        Expr::Kwarg(_, value) => eval(value, original, rng, password),
+        Expr::Field(directive) => fake_value(*directive, original, rng, password),
+        // `password` inspects its raw arguments (symbols and keywords), so it is
+        // dispatched before the generic value-based built-ins.
+        Expr::Call(name, args) if name == "password" => eval_password(args, password, rng),
+        Expr::Call(name, args) => {
+            let values = args
+                .iter()
+                .map(|arg| eval(arg, original, rng, password))
+                .collect::<Result<Vec<_>>>()?;
+
+            call_builtin(name, &values, rng)
+        }
+    }
+}
+
+/// Dispatch a built-in function by name over its already-evaluated arguments.
+fn call_builtin(name: &str, args: &[Value], rng: &mut dyn RngCore) -> Result<Value> {
+    match name {
+        "concat" => {
+            let mut out = String::new();
+
+            for arg in args {
+                out.push_str(&value_to_string(arg));
+            }
+
+            Ok(Value::String(out))
         }
-        Directive::PhoneNumber => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            let phone_number: String = PhoneNumber().fake_with_rng(rng);
-            write!(writer, "{}", MySqlString(phone_number))?;
+        "coalesce" => Ok(args
+            .iter()
+            .find(|v| !v.is_null())
+            .cloned()
+            .unwrap_or(Value::Null)),
+        "enum" => sample_enum(args, rng),
+        "u32" => {
+            let [min, max] = bounds(args)?;
+            let min = min.as_u64().wrap_err("`u32` bounds must be integers")? as u32;
+            let max = max.as_u64().wrap_err("`u32` bounds must be integers")? as u32;
+            Ok(json!(rng.gen_range(min..=max)))
+        }
+        "date" => {
+            let [lo, hi] = bounds(args)?;
+            let lo = parse_datetime(lo.as_str().wrap_err("`date` bounds must be strings")?)
+                .wrap_err("invalid lower `date` bound")?;
+            let hi = parse_datetime(hi.as_str().wrap_err("`date` bounds must be strings")?)
+                .wrap_err("invalid upper `date` bound")?;
+
+            let lo = lo.and_utc().timestamp();
+            let hi = hi.and_utc().timestamp();
+            let ts = rng.gen_range(lo.min(hi)..=lo.max(hi));
+            let date = ChronoDateTime::from_timestamp(ts, 0)
+                .wrap_err("sampled timestamp out of range")?
+                .naive_utc();
+
+            Ok(Value::String(date.format("%Y-%m-%d %H:%M:%S").to_string()))
+        }
+        _ => bail!("unknown function `{name}`"),
+    }
+}
+
+/// Sample one value from a weighted set of `[value, weight]` pairs, drawing a
+/// uniform point on the cumulative distribution of the (unnormalized) weights.
+fn sample_enum(args: &[Value], rng: &mut dyn RngCore) -> Result<Value> {
+    let mut choices = Vec::with_capacity(args.len());
+    let mut total = 0.0;
+
+    for arg in args {
+        let pair = arg
+            .as_array()
+            .wrap_err("`enum` arguments must be `[value, weight]` pairs")?;
+
+        let [value, weight] = pair.as_slice() else {
+            bail!("each `enum` pair needs a value and a weight");
+        };
+
+        let weight = weight.as_f64().wrap_err("`enum` weight must be a number")?;
+
+        if weight < 0.0 {
+            bail!("`enum` weights must be non-negative");
+        }
+
+        total += weight;
+        choices.push((value, weight));
+    }
+
+    if total <= 0.0 {
+        bail!("`enum` weights must sum to a positive value");
+    }
+
+    let mut point = rng.gen_range(0.0..total);
+
+    for (value, weight) in &choices {
+        if point < *weight {
+            return Ok((*value).clone());
+        }
+
+        point -= weight;
+    }
+
+    // Guard against floating-point drift landing past the last bucket.
+    Ok(choices.last().unwrap().0.clone())
+}
+
+/// Extract the two-argument bounds shared by the range-bounded built-ins.
+fn bounds(args: &[Value]) -> Result<[&Value; 2]> {
+    match args {
+        [lo, hi] => Ok([lo, hi]),
+        _ => bail!("expected exactly two bounds, got {}", args.len()),
+    }
+}
+
+/// The raw textual form of a value for `concat`, without SQL quoting.
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Render a logical value into the SQL token written to the dump: strings are
+/// emitted as escaped literals, numbers bare and nulls as `NULL`.
+fn render_sql(value: &Value) -> String {
+    match value {
+        Value::String(s) => MySqlString(s.clone()).to_string(),
+        Value::Null => "NULL".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// The password hashing scheme selected by a `password` capture.
+enum PasswordAlgorithm {
+    Bcrypt { cost: u32 },
+    Argon2,
+}
+
+/// Evaluate a `@password("plaintext", <algorithm>, cost=<n>)` call, falling back
+/// to the CLI plaintext and a bcrypt default when arguments are omitted.
+fn eval_password(args: &[Expr], default_plaintext: &str, rng: &mut impl Rng) -> Result<Value> {
+    let mut plaintext: Option<String> = None;
+    let mut algorithm = "bcrypt".to_string();
+    let mut cost = bcrypt::DEFAULT_COST;
+
+    for arg in args {
+        match arg {
+            Expr::Lit(Value::String(s)) if plaintext.is_none() => plaintext = Some(s.clone()),
+            Expr::Sym(s) => algorithm = s.clone(),
+            Expr::Kwarg(key, value) if key == "cost" => {
+                cost = expr_as_u32(value).wrap_err("`cost` must be a non-negative integer")?;
+            }
+            Expr::Kwarg(key, _) => bail!("unknown `password` keyword `{key}`"),
+            _ => bail!("unexpected `password` argument"),
+        }
+    }
+
+    let plaintext = plaintext.as_deref().unwrap_or(default_plaintext);
+
+    let algorithm = match algorithm.as_str() {
+        "bcrypt" => PasswordAlgorithm::Bcrypt { cost },
+        "argon2" => PasswordAlgorithm::Argon2,
+        other => bail!("unknown password algorithm `{other}`"),
+    };
+
+    Ok(Value::String(hash_password(plaintext, algorithm, rng)?))
+}
+
+/// Hash a plaintext into a valid, verifiable digest. The salt is drawn from
+/// the supplied `rng`, so under `--key` it inherits that RNG's determinism —
+/// identical plaintext and original bytes hash to the same digest across
+/// runs, while unkeyed runs still draw a fresh salt per row.
+fn hash_password(plaintext: &str, algorithm: PasswordAlgorithm, rng: &mut impl Rng) -> Result<String> {
+    match algorithm {
+        PasswordAlgorithm::Bcrypt { cost } => {
+            let mut salt = [0; 16];
+            rng.fill_bytes(&mut salt);
+            Ok(bcrypt::hash_with_salt(plaintext, cost, salt)?.to_string())
         }
-        Directive::U32 => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            write!(writer, "{}", rng.next_u32())?;
+        PasswordAlgorithm::Argon2 => {
+            let mut salt_bytes = [0; 16];
+            rng.fill_bytes(&mut salt_bytes);
+            let salt = SaltString::encode_b64(&salt_bytes)
+                .map_err(|e| eyre!("invalid argon2 salt: {e}"))?;
+
+            Argon2::default()
+                .hash_password(plaintext.as_bytes(), &salt)
+                .map(|hash| hash.to_string())
+                .map_err(|e| eyre!("argon2 hashing failed: {e}"))
         }
-        Directive::VatNo => {
-            reader.seek(SeekFrom::Start(node.end_byte() as u64))?;
-            write!(writer, "'AT01234567'")?;
+    }
+}
+
+/// Interpret a literal expression as a `u32`, used for the `cost` keyword.
+fn expr_as_u32(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::Lit(Value::Number(n)) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        _ => None,
+    }
+}
+
+/// Parse the original bytes of an order-preserving capture into the integer key
+/// used to sort and map it. Dates collapse to their UTC timestamp.
+fn order_preserving_key(directive: Directive, original: &[u8]) -> Option<i64> {
+    let s = str::from_utf8(original).ok()?;
+
+    match directive {
+        Directive::U32 => s.trim().parse::<u32>().ok().map(i64::from),
+        Directive::I64 => s.trim().parse::<i64>().ok(),
+        Directive::Date => {
+            let string: MySqlString = s.parse().ok()?;
+            parse_datetime(&string).map(|dt| dt.and_utc().timestamp())
         }
+        _ => None,
     }
+}
 
-    Ok(reader)
+/// Parse a MySQL date or datetime literal body into a `NaiveDateTime`, treating
+/// bare dates as midnight.
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Build a strictly increasing original -> fake mapping over the sorted
+/// distinct values, starting at a random small base and stepping by a random
+/// positive increment so that magnitudes are hidden but ordering is
+/// preserved. Steps are capped so the mapping never exceeds the directive's
+/// `order_preserving_max`, keeping e.g. `u32` columns in range on reload.
+fn build_order_preserving_map(
+    directive: Directive,
+    values: &HashSet<i64>,
+    rng: &mut impl Rng,
+) -> HashMap<i64, i64> {
+    let mut sorted = values.iter().copied().collect::<Vec<_>>();
+    sorted.sort_unstable();
+
+    let slots = sorted.len() as i64 + 1;
+    let step_cap = (directive.order_preserving_max() / slots).max(1);
+
+    let mut fake: i64 = rng.gen_range(0..=step_cap);
+    let mut map = HashMap::with_capacity(sorted.len());
+
+    for value in sorted {
+        fake += rng.gen_range(1..=step_cap);
+        map.insert(value, fake);
+    }
+
+    map
+}
+
+/// Substitute an order-preserving capture with its mapped fake value, or `None`
+/// when the directive is not order-preserving or the original failed to parse.
+fn apply_order_preserving(
+    directive: Directive,
+    original: &[u8],
+    maps: &HashMap<Directive, HashMap<i64, i64>>,
+) -> Result<Option<String>> {
+    let Some(map) = maps.get(&directive) else {
+        return Ok(None);
+    };
+
+    let Some(key) = order_preserving_key(directive, original) else {
+        return Ok(None);
+    };
+
+    let Some(&fake) = map.get(&key) else {
+        return Ok(None);
+    };
+
+    let rendered = match directive {
+        Directive::Date => {
+            let date = ChronoDateTime::from_timestamp(fake, 0)
+                .wrap_err("order-preserving timestamp out of range")?
+                .naive_utc();
+
+            MySqlString(date.format("%Y-%m-%d %H:%M:%S").to_string()).to_string()
+        }
+        _ => fake.to_string(),
+    };
+
+    Ok(Some(rendered))
 }
 
 pub struct MySqlString(String);
@@ -357,3 +1282,177 @@ impl fmt::Display for MySqlString {
         write!(f, "'{}'", self.0.as_bytes().escape_ascii())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bare_directive() {
+        assert_eq!("email".parse::<Expr>().unwrap(), Expr::Field(Directive::Email));
+    }
+
+    #[test]
+    fn parses_unknown_bare_word_as_symbol() {
+        assert_eq!("bcrypt".parse::<Expr>().unwrap(), Expr::Sym("bcrypt".to_string()));
+    }
+
+    #[test]
+    fn parses_string_literal_with_escapes() {
+        let expr = r#""a\"b\n\t\\c""#.parse::<Expr>().unwrap();
+        assert_eq!(expr, Expr::Lit(Value::String("a\"b\n\t\\c".to_string())));
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!("42".parse::<Expr>().unwrap(), Expr::Lit(json!(42)));
+        assert_eq!("-3.5".parse::<Expr>().unwrap(), Expr::Lit(json!(-3.5)));
+    }
+
+    #[test]
+    fn parses_array_of_literals() {
+        let expr = r#"["Male", 0.5]"#.parse::<Expr>().unwrap();
+        assert_eq!(
+            expr,
+            Expr::Lit(Value::Array(vec![Value::String("Male".to_string()), json!(0.5)]))
+        );
+    }
+
+    #[test]
+    fn rejects_array_of_non_literals() {
+        assert!("[email]".parse::<Expr>().is_err());
+    }
+
+    #[test]
+    fn parses_keyword_argument() {
+        assert_eq!(
+            "cost=10".parse::<Expr>().unwrap(),
+            Expr::Kwarg("cost".to_string(), Box::new(Expr::Lit(json!(10))))
+        );
+    }
+
+    #[test]
+    fn parses_call_with_args() {
+        let expr = "u32(18, 99)".parse::<Expr>().unwrap();
+        assert_eq!(
+            expr,
+            Expr::Call("u32".to_string(), vec![Expr::Lit(json!(18)), Expr::Lit(json!(99))])
+        );
+    }
+
+    #[test]
+    fn parses_call_with_field_and_literal_args() {
+        let expr = r#"concat(first_name, " ", last_name)"#.parse::<Expr>().unwrap();
+        assert_eq!(
+            expr,
+            Expr::Call(
+                "concat".to_string(),
+                vec![
+                    Expr::Field(Directive::FirstName),
+                    Expr::Lit(Value::String(" ".to_string())),
+                    Expr::Field(Directive::LastName),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_input() {
+        assert!("email extra".parse::<Expr>().is_err());
+    }
+
+    #[test]
+    fn rejects_unterminated_string() {
+        assert!(r#""unterminated"#.parse::<Expr>().is_err());
+    }
+
+    #[test]
+    fn biological_sex_alias_expands_to_50_50_enum() {
+        assert_eq!(
+            "biological_sex".parse::<Expr>().unwrap(),
+            biological_sex_alias()
+        );
+    }
+
+    #[test]
+    fn sample_enum_always_picks_the_only_choice() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let args = [json!(["A", 1.0])];
+
+        for _ in 0..100 {
+            assert_eq!(sample_enum(&args, &mut rng).unwrap(), json!("A"));
+        }
+    }
+
+    #[test]
+    fn sample_enum_draws_from_every_positive_weight() {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let args = [json!(["A", 1.0]), json!(["B", 1.0])];
+        let mut seen = HashSet::new();
+
+        for _ in 0..200 {
+            let Value::String(s) = sample_enum(&args, &mut rng).unwrap() else {
+                panic!("expected a string");
+            };
+            seen.insert(s);
+        }
+
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn sample_enum_never_draws_a_zero_weight_choice() {
+        let mut rng = ChaCha20Rng::seed_from_u64(2);
+        let args = [json!(["A", 1.0]), json!(["B", 0.0])];
+
+        for _ in 0..100 {
+            assert_eq!(sample_enum(&args, &mut rng).unwrap(), json!("A"));
+        }
+    }
+
+    #[test]
+    fn sample_enum_rejects_negative_weight() {
+        let args = [json!(["A", -1.0])];
+        assert!(sample_enum(&args, &mut ChaCha20Rng::seed_from_u64(0)).is_err());
+    }
+
+    #[test]
+    fn sample_enum_rejects_all_zero_weights() {
+        let args = [json!(["A", 0.0]), json!(["B", 0.0])];
+        assert!(sample_enum(&args, &mut ChaCha20Rng::seed_from_u64(0)).is_err());
+    }
+
+    #[test]
+    fn order_preserving_map_is_monotonic() {
+        let values = HashSet::from([5, 1, 3]);
+        let map = build_order_preserving_map(Directive::I64, &values, &mut ChaCha20Rng::seed_from_u64(0));
+
+        assert!(map[&1] < map[&3]);
+        assert!(map[&3] < map[&5]);
+    }
+
+    #[test]
+    fn order_preserving_map_stays_within_u32_range() {
+        let values = (0..10_000).map(i64::from).collect::<HashSet<_>>();
+        let map = build_order_preserving_map(Directive::U32, &values, &mut ChaCha20Rng::seed_from_u64(0));
+
+        assert!(map.values().all(|&fake| fake >= 0 && fake <= i64::from(u32::MAX)));
+    }
+
+    #[test]
+    fn parse_datetime_parses_full_timestamp() {
+        let dt = parse_datetime("2020-01-02 03:04:05").unwrap();
+        assert_eq!(dt.format("%Y-%m-%d %H:%M:%S").to_string(), "2020-01-02 03:04:05");
+    }
+
+    #[test]
+    fn parse_datetime_treats_bare_date_as_midnight() {
+        let dt = parse_datetime("2020-01-02").unwrap();
+        assert_eq!(dt.format("%Y-%m-%d %H:%M:%S").to_string(), "2020-01-02 00:00:00");
+    }
+
+    #[test]
+    fn parse_datetime_rejects_garbage() {
+        assert!(parse_datetime("not a date").is_none());
+    }
+}